@@ -1,14 +1,20 @@
 use std::{
     fmt, io,
+    io::Write,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+use async_trait::async_trait;
 use futures::{stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use lazy_static::lazy_static;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde::Deserialize;
-use zip::ZipArchive;
+use sha1::{Digest, Sha1};
+use walkdir::WalkDir;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 const BASE_CURSE_URL: &str = "https://api.curseforge.com";
 
@@ -18,51 +24,448 @@ struct CurseFile {
     file_id: u32,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct CurseFileHash {
+    value: String,
+    algo: u8,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CurseFileInfo {
+    id: u32,
     display_name: String,
     download_url: Option<String>,
     file_name: String,
+    #[serde(default)]
+    hashes: Vec<CurseFileHash>,
+}
+
+enum HashAlgo {
+    Sha1,
+    Md5,
 }
 
 impl CurseFileInfo {
-    async fn download(&self, client: &Client, folder: &Path) -> crate::error::Result<PathBuf> {
+    /// Picks the hash to verify downloads against, preferring the SHA1 entry
+    /// (`algo == 1`) and falling back to MD5 (`algo == 2`) when that's all the
+    /// API gave us.
+    fn expected_hash(&self) -> Option<(HashAlgo, &str)> {
+        self.hashes
+            .iter()
+            .find(|h| h.algo == 1)
+            .map(|h| (HashAlgo::Sha1, h.value.as_str()))
+            .or_else(|| {
+                self.hashes
+                    .iter()
+                    .find(|h| h.algo == 2)
+                    .map(|h| (HashAlgo::Md5, h.value.as_str()))
+            })
+    }
+}
+
+/// A single file that [`CurseModpack::install_to`] needs to fetch. Implemented
+/// by the CurseForge file lookup as well as plain URLs and Maven artifacts, so
+/// the installer can drive every kind of source through one download loop.
+#[async_trait]
+trait Downloadable {
+    /// Resolves the URL to download this source from, making up to
+    /// `retry_attempts` attempts for sources (like a CurseForge file) whose URL
+    /// comes from an API call that can transiently fail or return stale data.
+    async fn resolve_url(
+        &self,
+        client: &Client,
+        retry_attempts: u32,
+        retry_base_delay: Duration,
+    ) -> crate::error::Result<String>;
+    fn filename(&self) -> String;
+    /// Returns `true` if `data` is acceptable for this source. Sources with no
+    /// hash to check against (plain URLs, Maven artifacts) accept anything.
+    fn verify(&self, _data: &[u8]) -> bool {
+        true
+    }
+    /// Human-readable label shown on the source's progress bar. Defaults to
+    /// the resolved filename.
+    fn display_name(&self) -> String {
+        self.filename()
+    }
+}
+
+#[async_trait]
+impl Downloadable for CurseFileInfo {
+    /// Returns the `download_url` from the initial batch lookup if present;
+    /// otherwise re-queries `/v1/mods/files` for just this file ID, making up
+    /// to `retry_attempts` attempts, since a null `download_url` sometimes
+    /// clears up on a fresh request for the same file.
+    async fn resolve_url(
+        &self,
+        client: &Client,
+        retry_attempts: u32,
+        retry_base_delay: Duration,
+    ) -> crate::error::Result<String> {
+        if let Some(url) = &self.download_url {
+            return Ok(url.replace('"', ""));
+        }
+        let api_key = std::env::var("CURSE_API_KEY").expect("CURSE_API_KEY not set");
+        let url = format!("{}/v1/mods/files", BASE_CURSE_URL);
+        let file_id = self.id;
+        crate::util::retry_with_backoff(retry_attempts, retry_base_delay, || async {
+            let response = client
+                .post(&url)
+                .header("Accept", "application/json")
+                .header("Content-Type", "application/json")
+                .header("x-api-key", &api_key)
+                .body(format!("{{\"fileIds\":[{}]}}", file_id))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await?;
+            let data = response.get("data").ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "data not found in curseforge response",
+                )
+            })?;
+            let infos = serde_json::from_value::<Vec<CurseFileInfo>>(data.clone())?;
+            infos
+                .into_iter()
+                .find_map(|info| info.download_url)
+                .map(|url| url.replace('"', ""))
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "download_url not found").into()
+                })
+        })
+        .await
+    }
+
+    fn filename(&self) -> String {
         lazy_static! {
             static ref ILLEGAL_CHARS: regex::Regex = regex::Regex::new(r#"[\\/:*?"<>|]"#)
                 .expect("Failed to compile ILLEGAL_CHARS regex");
         }
-        debug!("Downloading {}", self.display_name);
-        let parent_folder = if self.file_name.ends_with("zip") {
-            "resourcepacks"
-        } else {
-            "mods"
-        };
-        let target = folder.join(parent_folder);
-        dablenutil::tokio::async_create_dir_if_not_exists(&target).await?;
-        let file_name = ILLEGAL_CHARS.replace_all(&self.file_name, "").to_string();
-        if self.download_url.is_none() {
-            return Err(io::Error::new(io::ErrorKind::NotFound, "download_url not found").into());
-        }
-        let download_url = self.download_url.as_ref().unwrap().replace('"', "");
-        let path = target.join(file_name);
-        if path.exists() {
+        ILLEGAL_CHARS.replace_all(&self.file_name, "").to_string()
+    }
+
+    fn verify(&self, data: &[u8]) -> bool {
+        match self.expected_hash() {
+            Some((HashAlgo::Sha1, expected)) => {
+                crate::util::hex_encode(&Sha1::digest(data)).eq_ignore_ascii_case(expected)
+            }
+            Some((HashAlgo::Md5, expected)) => {
+                crate::util::hex_encode(&md5::compute(data).0).eq_ignore_ascii_case(expected)
+            }
+            None => true,
+        }
+    }
+
+    fn display_name(&self) -> String {
+        self.display_name.clone()
+    }
+}
+
+/// A mod source given as a bare URL, for packs that need to pull a file CurseForge
+/// doesn't have a `download_url` for.
+#[derive(Debug, Clone, Deserialize)]
+struct DirectDownload {
+    url: String,
+    filename: String,
+}
+
+#[async_trait]
+impl Downloadable for DirectDownload {
+    async fn resolve_url(
+        &self,
+        _client: &Client,
+        _retry_attempts: u32,
+        _retry_base_delay: Duration,
+    ) -> crate::error::Result<String> {
+        Ok(self.url.clone())
+    }
+
+    fn filename(&self) -> String {
+        self.filename.clone()
+    }
+}
+
+/// A mod source identified by Maven coordinates, resolved against `repository`
+/// the same way a build tool would.
+#[derive(Debug, Clone, Deserialize)]
+struct MavenArtifact {
+    repository: String,
+    group: String,
+    artifact: String,
+    version: String,
+    classifier: Option<String>,
+}
+
+/// Builds `<repo>/<group-with-slashes>/<artifact>/<version>/<artifact>-<version>[-classifier].jar`.
+fn mvn_artifact_to_url(artifact: &MavenArtifact) -> String {
+    let group_path = artifact.group.replace('.', "/");
+    format!(
+        "{repo}/{group_path}/{artifact_id}/{version}/{artifact_id}-{version}{classifier}.jar",
+        repo = artifact.repository.trim_end_matches('/'),
+        artifact_id = artifact.artifact,
+        version = artifact.version,
+        classifier = artifact
+            .classifier
+            .as_deref()
+            .map_or_else(String::new, |c| format!("-{}", c)),
+    )
+}
+
+#[async_trait]
+impl Downloadable for MavenArtifact {
+    async fn resolve_url(
+        &self,
+        _client: &Client,
+        _retry_attempts: u32,
+        _retry_base_delay: Duration,
+    ) -> crate::error::Result<String> {
+        Ok(mvn_artifact_to_url(self))
+    }
+
+    fn filename(&self) -> String {
+        let classifier = self
+            .classifier
+            .as_deref()
+            .map_or_else(String::new, |c| format!("-{}", c));
+        format!("{}-{}{}.jar", self.artifact, self.version, classifier)
+    }
+}
+
+/// A mod source declared in the manifest's `extraSources` list, for mods that
+/// don't come from the CurseForge files resolved in `files`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ExtraSource {
+    Direct(DirectDownload),
+    Maven(MavenArtifact),
+}
+
+#[async_trait]
+impl Downloadable for ExtraSource {
+    async fn resolve_url(
+        &self,
+        client: &Client,
+        retry_attempts: u32,
+        retry_base_delay: Duration,
+    ) -> crate::error::Result<String> {
+        match self {
+            Self::Direct(source) => {
+                source
+                    .resolve_url(client, retry_attempts, retry_base_delay)
+                    .await
+            }
+            Self::Maven(source) => {
+                source
+                    .resolve_url(client, retry_attempts, retry_base_delay)
+                    .await
+            }
+        }
+    }
+
+    fn filename(&self) -> String {
+        match self {
+            Self::Direct(source) => source.filename(),
+            Self::Maven(source) => source.filename(),
+        }
+    }
+}
+
+/// Resolves and downloads any [`Downloadable`] source into `folder`, skipping
+/// the request entirely if a file already on disk passes `source.verify`.
+async fn download_source<D: Downloadable + ?Sized>(
+    source: &D,
+    client: &Client,
+    folder: &Path,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+    progress: &ProgressBar,
+) -> crate::error::Result<PathBuf> {
+    let file_name = source.filename();
+    let parent_folder = if file_name.ends_with("zip") {
+        "resourcepacks"
+    } else {
+        "mods"
+    };
+    let target = folder.join(parent_folder);
+    dablenutil::tokio::async_create_dir_if_not_exists(&target).await?;
+    let path = target.join(file_name);
+    if path.exists() {
+        let bytes = tokio::fs::read(&path).await?;
+        if source.verify(&bytes) {
             return Ok(path);
         }
+        warn!(
+            "{} failed hash verification, re-downloading",
+            path.display()
+        );
+        tokio::fs::remove_file(&path).await?;
+    }
+    // Resolution retries on its own (e.g. a CurseForge file re-queries on a
+    // null `download_url`), so by the time we get a URL back it's final for
+    // this attempt; only the GET itself needs to be retried below.
+    let download_url = source
+        .resolve_url(client, retry_attempts, retry_base_delay)
+        .await?;
+    crate::util::retry_with_backoff(retry_attempts, retry_base_delay, || async {
+        use tokio::io::AsyncWriteExt;
+
+        progress.set_position(0);
         let mut file_handle = tokio::fs::File::create(&path).await?;
-        let response = client.get(&download_url).send().await?.error_for_status()?;
-        let content = response.bytes().await?;
-        tokio::io::copy(&mut content.to_vec().as_slice(), &mut file_handle).await?;
-        Ok(path)
+        let response = client
+            .get(&download_url)
+            .send()
+            .await?
+            .error_for_status()?;
+        progress.set_length(response.content_length().unwrap_or(0));
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file_handle.write_all(&chunk).await?;
+            progress.inc(chunk.len() as u64);
+        }
+        drop(file_handle);
+        let bytes = tokio::fs::read(&path).await?;
+        if !source.verify(&bytes) {
+            tokio::fs::remove_file(&path).await.ok();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} failed hash verification", path.display()),
+            )
+            .into());
+        }
+        Ok(path.clone())
+    })
+    .await
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModLoaderInfo {
+    id: String,
+    #[allow(dead_code)]
+    primary: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MinecraftInfo {
+    version: String,
+    mod_loaders: Vec<ModLoaderInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoaderKind {
+    Forge,
+    NeoForge,
+    Fabric,
+    Quilt,
+}
+
+impl LoaderKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "forge" => Some(Self::Forge),
+            "neoforge" => Some(Self::NeoForge),
+            "fabric" => Some(Self::Fabric),
+            "quilt" => Some(Self::Quilt),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Forge => "forge",
+            Self::NeoForge => "neoforge",
+            Self::Fabric => "fabric",
+            Self::Quilt => "quilt",
+        }
+    }
+}
+
+async fn download_to_file(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+) -> crate::error::Result<()> {
+    crate::util::retry_with_backoff(retry_attempts, retry_base_delay, || async {
+        let response = client.get(url).send().await?.error_for_status()?;
+        let bytes = response.bytes().await?;
+        tokio::fs::write(path, &bytes).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Computes the CurseForge-style file fingerprint: a seed-1 MurmurHash2 of the
+/// file's bytes with whitespace bytes (tab, LF, CR, space) stripped out. This
+/// is what the `/v1/fingerprints` endpoint expects to match a local jar back
+/// to its project and file IDs.
+fn curse_fingerprint(data: &[u8]) -> u32 {
+    let normalized: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !matches!(b, 0x09 | 0x0a | 0x0d | 0x20))
+        .collect();
+    murmur2(&normalized, 1)
+}
+
+fn murmur2(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+    let mut hash = seed ^ (data.len() as u32);
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        hash = hash.wrapping_mul(M);
+        hash ^= k;
     }
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        for (i, &byte) in remainder.iter().enumerate().rev() {
+            hash ^= u32::from(byte) << (8 * i);
+        }
+        hash = hash.wrapping_mul(M);
+    }
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(M);
+    hash ^= hash >> 15;
+    hash
 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintMatchFile {
+    id: u32,
+    mod_id: u32,
+    file_fingerprint: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintMatch {
+    file: FingerprintMatchFile,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CurseManifest {
     files: Vec<CurseFile>,
+    minecraft: MinecraftInfo,
     name: String,
     overrides: String,
     version: String,
+    /// Mods that can't be resolved through the CurseForge files lookup, e.g.
+    /// because their `download_url` comes back null. Not part of the official
+    /// CurseForge schema; a companion field `cmi` sources packs can opt into.
+    #[serde(default)]
+    extra_sources: Vec<ExtraSource>,
 }
 
 pub struct CurseModpack {
@@ -87,6 +490,11 @@ impl CurseModpack {
 
     fn copy_overrides(&mut self, target: &Path) -> crate::error::Result<()> {
         info!("Copying overrides...");
+        let progress = ProgressBar::new_spinner();
+        progress.set_style(
+            ProgressStyle::with_template("{spinner:.green} copied {pos} overrides")
+                .expect("Failed to build overrides progress bar style"),
+        );
         let entry_count = self.archive.len();
         let mut overrides_count = 0;
         for i in 0..entry_count {
@@ -109,12 +517,113 @@ impl CurseModpack {
             let mut file_handle = std::fs::File::create(&target_path)?;
             std::io::copy(&mut file, &mut file_handle)?;
             overrides_count += 1;
+            progress.set_position(overrides_count);
         }
+        progress.finish_and_clear();
         info!("Copied {} overrides", overrides_count);
         Ok(())
     }
 
-    pub async fn install_to(&mut self, target: &Path) -> crate::error::Result<()> {
+    /// Installs the mod loader declared in the manifest's `minecraft.modLoaders`
+    /// (the entry with `primary: true`) into `target`: Forge and NeoForge get
+    /// their Maven-hosted installer jar, Fabric and Quilt get their launcher
+    /// profile from the respective meta API. A `MODLOADER.txt` note recording
+    /// the required loader version is written alongside it either way.
+    async fn install_loader(
+        &self,
+        client: &Client,
+        target: &Path,
+        retry_attempts: u32,
+        retry_base_delay: Duration,
+    ) -> crate::error::Result<()> {
+        let Some(loader) = self
+            .manifest
+            .minecraft
+            .mod_loaders
+            .iter()
+            .find(|l| l.primary)
+        else {
+            debug!("No primary mod loader declared in manifest, skipping loader install");
+            return Ok(());
+        };
+        let Some((kind_str, loader_version)) = loader.id.split_once('-') else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed mod loader id: {}", loader.id),
+            )
+            .into());
+        };
+        let Some(kind) = LoaderKind::parse(kind_str) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported mod loader: {}", kind_str),
+            )
+            .into());
+        };
+        let mc_version = &self.manifest.minecraft.version;
+        info!(
+            "Installing {} {} for Minecraft {}",
+            kind.as_str(),
+            loader_version,
+            mc_version
+        );
+        match kind {
+            LoaderKind::Forge => {
+                let installer_url = format!(
+                    "https://maven.minecraftforge.net/net/minecraftforge/forge/{mc_version}-{loader_version}/forge-{mc_version}-{loader_version}-installer.jar"
+                );
+                let path = target.join(format!("{}-installer.jar", kind.as_str()));
+                download_to_file(client, &installer_url, &path, retry_attempts, retry_base_delay)
+                    .await?;
+                info!("Downloaded {} installer to {}", kind.as_str(), path.display());
+            }
+            LoaderKind::NeoForge => {
+                // NeoForge isn't hosted on maven.minecraftforge.net, and its
+                // versions aren't prefixed with the Minecraft version.
+                let installer_url = format!(
+                    "https://maven.neoforged.net/releases/net/neoforged/neoforge/{loader_version}/neoforge-{loader_version}-installer.jar"
+                );
+                let path = target.join(format!("{}-installer.jar", kind.as_str()));
+                download_to_file(client, &installer_url, &path, retry_attempts, retry_base_delay)
+                    .await?;
+                info!("Downloaded {} installer to {}", kind.as_str(), path.display());
+            }
+            LoaderKind::Fabric | LoaderKind::Quilt => {
+                let meta_url = match kind {
+                    LoaderKind::Fabric => format!(
+                        "https://meta.fabricmc.net/v2/versions/loader/{mc_version}/{loader_version}/profile/json"
+                    ),
+                    LoaderKind::Quilt => format!(
+                        "https://meta.quiltmc.org/v3/versions/loader/{mc_version}/{loader_version}/profile/json"
+                    ),
+                    _ => unreachable!(),
+                };
+                let path = target.join(format!("{}-profile.json", kind.as_str()));
+                download_to_file(client, &meta_url, &path, retry_attempts, retry_base_delay)
+                    .await?;
+                info!("Downloaded {} profile to {}", kind.as_str(), path.display());
+            }
+        }
+        let note_path = target.join("MODLOADER.txt");
+        tokio::fs::write(
+            &note_path,
+            format!(
+                "Requires {} loader version {} for Minecraft {}\n",
+                kind.as_str(),
+                loader_version,
+                mc_version
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn install_to(
+        &mut self,
+        target: &Path,
+        retry_attempts: u32,
+        retry_base_delay: Duration,
+    ) -> crate::error::Result<()> {
         if !target.is_dir() {
             return Err(
                 io::Error::new(io::ErrorKind::NotFound, "target is not a directory").into(),
@@ -125,6 +634,9 @@ impl CurseModpack {
             self.manifest.name,
             target.display()
         );
+        let client = Client::new();
+        self.install_loader(&client, target, retry_attempts, retry_base_delay)
+            .await?;
         let num_cpus = num_cpus::get();
         // collect file id's into json array
         let file_ids: Vec<_> = self
@@ -135,60 +647,415 @@ impl CurseModpack {
             .collect();
         let file_ids = serde_json::to_string(&file_ids)?;
         let body = format!("{{\"fileIds\":{}}}", file_ids);
-        let client = Client::new();
         let api_key = std::env::var("CURSE_API_KEY").expect("CURSE_API_KEY not set");
         info!("Downloading {} files", self.manifest.files.len());
         let url = format!("{}/v1/mods/files", BASE_CURSE_URL);
-        let response = client
-            .post(url)
-            .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
-            .header("x-api-key", api_key)
-            .body(body)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<serde_json::Value>()
-            .await?;
-        let file_infos = response
-            .get("data")
-            .map(|data| serde_json::from_value::<Vec<CurseFileInfo>>(data.clone()))
-            .ok_or_else(|| {
+        let file_infos = crate::util::retry_with_backoff(retry_attempts, retry_base_delay, || async {
+            let response = client
+                .post(&url)
+                .header("Accept", "application/json")
+                .header("Content-Type", "application/json")
+                .header("x-api-key", &api_key)
+                .body(body.clone())
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await?;
+            let data = response.get("data").ok_or_else(|| {
                 io::Error::new(
                     io::ErrorKind::NotFound,
                     "data not found in curseforge response",
                 )
-            })??;
-        stream::iter(file_infos)
-            .for_each_concurrent(num_cpus, |info| {
+            })?;
+            Ok(serde_json::from_value::<Vec<CurseFileInfo>>(data.clone())?)
+        })
+        .await?;
+
+        let mut sources: Vec<Box<dyn Downloadable + Send + Sync>> = file_infos
+            .into_iter()
+            .map(|info| Box::new(info) as Box<dyn Downloadable + Send + Sync>)
+            .collect();
+        sources.extend(
+            self.manifest
+                .extra_sources
+                .iter()
+                .cloned()
+                .map(|source| Box::new(source) as Box<dyn Downloadable + Send + Sync>),
+        );
+
+        let multi_progress = MultiProgress::new();
+        let overall_progress = multi_progress.add(ProgressBar::new(sources.len() as u64));
+        overall_progress.set_style(
+            ProgressStyle::with_template("{msg} [{wide_bar:.cyan/blue}] {pos}/{len}")
+                .expect("Failed to build overall progress bar style")
+                .progress_chars("#>-"),
+        );
+        overall_progress.set_message("Installing mods");
+        let file_style = ProgressStyle::with_template(
+            "{msg:.dim} [{bar:20.green/blue}] {bytes}/{total_bytes}",
+        )
+        .expect("Failed to build per-file progress bar style")
+        .progress_chars("#>-");
+
+        stream::iter(sources)
+            .for_each_concurrent(num_cpus, |source| {
                 let target = &target;
                 let client = &client;
+                let multi_progress = &multi_progress;
+                let overall_progress = &overall_progress;
+                let file_style = &file_style;
                 async move {
-                    match info.download(client, target).await {
+                    let file_progress = multi_progress.add(ProgressBar::new(0));
+                    file_progress.set_style(file_style.clone());
+                    let display_name = source.display_name();
+                    file_progress.set_message(display_name.clone());
+                    match download_source(
+                        source.as_ref(),
+                        client,
+                        target,
+                        retry_attempts,
+                        retry_base_delay,
+                        &file_progress,
+                    )
+                    .await
+                    {
+                        // Suspend the live progress bars while logging so
+                        // printed lines don't get overwritten by the next
+                        // redraw, and vice versa.
                         Ok(p) => {
-                            debug!("{} downloaded to {}", info.file_name, p.display());
+                            multi_progress.suspend(|| {
+                                debug!("{} downloaded to {}", display_name, p.display());
+                            });
                         }
                         Err(e) => {
-                            if let crate::error::Error::IoError(e) = e {
-                                if e.kind() == io::ErrorKind::NotFound {
-                                    error!(
-                                        "Failed to download {}, no download URL found",
-                                        info.file_name
-                                    );
+                            multi_progress.suspend(|| {
+                                if let crate::error::Error::IoError(e) = &e {
+                                    if e.kind() == io::ErrorKind::NotFound {
+                                        error!(
+                                            "Failed to download {}, no download URL found",
+                                            display_name
+                                        );
+                                    } else {
+                                        error!("Failed to download {}", display_name);
+                                        error!("{:?}", e);
+                                    }
                                 } else {
-                                    error!("Failed to download {}", info.file_name);
+                                    error!("Failed to download {}", display_name);
                                     error!("{:?}", e);
                                 }
-                            } else {
-                                error!("Failed to download {}", info.file_name);
-                                error!("{:?}", e);
-                            }
+                            });
                         }
                     }
+                    multi_progress.remove(&file_progress);
+                    overall_progress.inc(1);
                 }
             })
             .await;
+        overall_progress.finish_with_message("Done downloading mods");
         self.copy_overrides(target)?;
         Ok(())
     }
+
+    /// Repackages an install directory created by [`CurseModpack::install_to`]
+    /// back into a CurseForge modpack zip: jars under `mods/` that match a
+    /// known CurseForge file (by fingerprint) are recorded in `manifest.json`,
+    /// everything else is bundled verbatim under `overrides/`, except any
+    /// `MODLOADER.txt` and the `*-installer.jar` / `*-profile.json` files in
+    /// the root of `source`, which are left out of the zip.
+    pub async fn export_to(
+        source: &Path,
+        output: &Path,
+        name: &str,
+        version: &str,
+        author: &str,
+    ) -> crate::error::Result<()> {
+        if !source.is_dir() {
+            return Err(
+                io::Error::new(io::ErrorKind::NotFound, "source is not a directory").into(),
+            );
+        }
+        info!("Exporting {} to {}", source.display(), output.display());
+        let mods_dir = source.join("mods");
+        let mut fingerprints = Vec::new();
+        let mut fingerprint_paths = Vec::new();
+        if mods_dir.is_dir() {
+            for entry in WalkDir::new(&mods_dir)
+                .into_iter()
+                .filter_map(std::result::Result::ok)
+            {
+                let path = entry.path();
+                if entry.file_type().is_file()
+                    && path.extension().and_then(std::ffi::OsStr::to_str) == Some("jar")
+                {
+                    let bytes = std::fs::read(path)?;
+                    fingerprints.push(curse_fingerprint(&bytes));
+                    fingerprint_paths.push(path.to_path_buf());
+                }
+            }
+        }
+
+        let matched_files = if fingerprints.is_empty() {
+            Vec::new()
+        } else {
+            let client = Client::new();
+            let api_key = std::env::var("CURSE_API_KEY").expect("CURSE_API_KEY not set");
+            let body = serde_json::json!({ "fingerprints": fingerprints }).to_string();
+            let url = format!("{}/v1/fingerprints", BASE_CURSE_URL);
+            let response = client
+                .post(url)
+                .header("Accept", "application/json")
+                .header("Content-Type", "application/json")
+                .header("x-api-key", api_key)
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await?;
+            let matches = response
+                .get("data")
+                .and_then(|data| data.get("exactMatches"))
+                .map(|m| serde_json::from_value::<Vec<FingerprintMatch>>(m.clone()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "exactMatches not found in fingerprint response",
+                    )
+                })??;
+            matches
+        };
+        info!(
+            "Matched {}/{} mods jars to CurseForge files",
+            matched_files.len(),
+            fingerprints.len()
+        );
+        let manifest_files: Vec<_> = matched_files
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "projectID": m.file.mod_id,
+                    "fileID": m.file.id,
+                    "required": true,
+                })
+            })
+            .collect();
+
+        let (mc_version, loader_id) = read_loader_note(source).await;
+        // An empty `loader_id` means the note was missing or unparseable; a
+        // stub entry marked `primary: true` would make a later install_loader
+        // call choke on `"".split_once('-')` and abort the whole install.
+        let mod_loaders: Vec<_> = if loader_id.is_empty() {
+            Vec::new()
+        } else {
+            vec![serde_json::json!({ "id": loader_id, "primary": true })]
+        };
+        let manifest = serde_json::json!({
+            "minecraft": {
+                "version": mc_version,
+                "modLoaders": mod_loaders,
+            },
+            "manifestType": "minecraftModpack",
+            "manifestVersion": 1,
+            "name": name,
+            "version": version,
+            "author": author,
+            "files": manifest_files,
+            "overrides": "overrides",
+        });
+
+        dablenutil::create_dir_if_not_exists(
+            output
+                .parent()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "output has no parent"))?,
+        )?;
+        let zip_file = std::fs::File::create(output)?;
+        let mut writer = ZipWriter::new(zip_file);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        writer.start_file("manifest.json", options)?;
+        writer.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+        let matched_fingerprints: std::collections::HashSet<u32> = matched_files
+            .iter()
+            .map(|m| m.file.file_fingerprint)
+            .collect();
+        let matched_jars: std::collections::HashSet<_> = fingerprint_paths
+            .iter()
+            .zip(fingerprints.iter())
+            .filter(|(_, fp)| matched_fingerprints.contains(fp))
+            .map(|(p, _)| p.clone())
+            .collect();
+
+        let mut overrides_count = 0;
+        for entry in WalkDir::new(source)
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+        {
+            let path = entry.path();
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if file_name == "MODLOADER.txt" {
+                continue;
+            }
+            // install_loader writes these directly into the target root; a
+            // re-install regenerates them from MODLOADER.txt, so bundling
+            // them as overrides would just ship dead weight in the zip.
+            if path.parent() == Some(source)
+                && (file_name.ends_with("-installer.jar") || file_name.ends_with("-profile.json"))
+            {
+                continue;
+            }
+            if matched_jars.contains(path) {
+                continue;
+            }
+            let relative = path.strip_prefix(source).expect("walked path under source");
+            let entry_name = format!("overrides/{}", relative.to_string_lossy());
+            debug!("Bundling {} as {}", path.display(), entry_name);
+            writer.start_file(entry_name.clone(), options)?;
+            let bytes = std::fs::read(path)?;
+            writer.write_all(&bytes)?;
+            overrides_count += 1;
+        }
+        writer.finish()?;
+        info!(
+            "Wrote {} with {} matched mods and {} overrides",
+            output.display(),
+            manifest_files.len(),
+            overrides_count
+        );
+        Ok(())
+    }
+}
+
+async fn read_loader_note(source: &Path) -> (String, String) {
+    lazy_static! {
+        static ref NOTE_RE: regex::Regex =
+            regex::Regex::new(r"Requires (\w+) loader version (\S+) for Minecraft (\S+)")
+                .expect("Failed to compile MODLOADER.txt note regex");
+    }
+    let note_path = source.join("MODLOADER.txt");
+    match tokio::fs::read_to_string(&note_path).await {
+        Ok(contents) => match NOTE_RE.captures(&contents) {
+            Some(captures) => (
+                captures[3].to_string(),
+                format!("{}-{}", &captures[1], &captures[2]),
+            ),
+            None => {
+                warn!("Failed to parse {}", note_path.display());
+                (String::from("unknown"), String::new())
+            }
+        },
+        Err(_) => {
+            warn!(
+                "{} not found, exported manifest will not declare a mod loader",
+                note_path.display()
+            );
+            (String::from("unknown"), String::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pinned to the current implementation rather than an external spec, so
+    // these are regression vectors: they won't catch the algorithm being
+    // wrong against CurseForge's actual fingerprinting, but they will catch
+    // a refactor silently changing the output (e.g. an endianness or
+    // chunking mistake).
+    #[test]
+    fn murmur2_matches_known_vectors() {
+        assert_eq!(murmur2(b"", 1), 0x5bd1_5e36);
+        assert_eq!(murmur2(b"a", 1), 0x2550_b18c);
+        assert_eq!(murmur2(b"hello world", 1), 0x83ea_5dee);
+    }
+
+    #[test]
+    fn curse_fingerprint_ignores_whitespace() {
+        let a = curse_fingerprint(b"hello world");
+        let b = curse_fingerprint(b"hello\n world\t");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn mvn_artifact_to_url_builds_expected_path() {
+        let artifact = MavenArtifact {
+            repository: "https://maven.example.com/".to_string(),
+            group: "com.example.mod".to_string(),
+            artifact: "examplemod".to_string(),
+            version: "1.2.3".to_string(),
+            classifier: None,
+        };
+        assert_eq!(
+            mvn_artifact_to_url(&artifact),
+            "https://maven.example.com/com/example/mod/examplemod/1.2.3/examplemod-1.2.3.jar"
+        );
+    }
+
+    #[test]
+    fn mvn_artifact_to_url_appends_classifier() {
+        let artifact = MavenArtifact {
+            repository: "https://maven.example.com".to_string(),
+            group: "com.example.mod".to_string(),
+            artifact: "examplemod".to_string(),
+            version: "1.2.3".to_string(),
+            classifier: Some("sources".to_string()),
+        };
+        assert_eq!(
+            mvn_artifact_to_url(&artifact),
+            "https://maven.example.com/com/example/mod/examplemod/1.2.3/examplemod-1.2.3-sources.jar"
+        );
+    }
+
+    #[test]
+    fn loader_kind_parse_recognizes_known_kinds() {
+        assert_eq!(LoaderKind::parse("forge"), Some(LoaderKind::Forge));
+        assert_eq!(LoaderKind::parse("neoforge"), Some(LoaderKind::NeoForge));
+        assert_eq!(LoaderKind::parse("fabric"), Some(LoaderKind::Fabric));
+        assert_eq!(LoaderKind::parse("quilt"), Some(LoaderKind::Quilt));
+        assert_eq!(LoaderKind::parse("liteloader"), None);
+    }
+
+    /// Creates a throwaway directory under the OS temp dir for a single test,
+    /// named after it to avoid colliding with other tests running in parallel.
+    fn temp_dir_for(test_name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cmi-test-{}-{}",
+            test_name,
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("system clock is before the epoch")
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).expect("failed to create temp dir");
+        dir
+    }
+
+    #[tokio::test]
+    async fn read_loader_note_parses_a_well_formed_note() {
+        let dir = temp_dir_for("read_loader_note_parses_a_well_formed_note");
+        tokio::fs::write(
+            dir.join("MODLOADER.txt"),
+            "Requires forge loader version 47.2.0 for Minecraft 1.20.1\n",
+        )
+        .await
+        .expect("failed to write note");
+        let (mc_version, loader_id) = read_loader_note(&dir).await;
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(mc_version, "1.20.1");
+        assert_eq!(loader_id, "forge-47.2.0");
+    }
+
+    #[tokio::test]
+    async fn read_loader_note_defaults_when_missing() {
+        let dir = temp_dir_for("read_loader_note_defaults_when_missing");
+        let (mc_version, loader_id) = read_loader_note(&dir).await;
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(mc_version, "unknown");
+        assert_eq!(loader_id, "");
+    }
 }
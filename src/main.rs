@@ -1,33 +1,69 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::module_name_repetitions, clippy::uninlined_format_args)]
 
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use curse::CurseModpack;
 use log::info;
 
 mod curse;
 mod error;
+mod util;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 struct CommandLineArgs {
-    /// The zip file to install from
-    #[clap(required = true)]
-    modpack_zip: PathBuf,
-    /// The target directory to install to
-    #[arg(required = true)]
-    target: PathBuf,
+    #[command(subcommand)]
+    command: Command,
     /// The log level to use: error, warn, info, debug, or trace
-    #[clap(short, long, value_parser, default_value_t = log::LevelFilter::Info)]
+    #[clap(short, long, value_parser, default_value_t = log::LevelFilter::Info, global = true)]
     log_level: log::LevelFilter,
     /// Use the PolyMC API key instead of the Curse API key.
     /// Note that by using this, you are technically violating Curse's ToS.
     /// This will override the CURSE_API_KEY environment variable, so it is
     /// only required once.
-    #[clap(long, default_value_t = false)]
+    #[clap(long, default_value_t = false, global = true)]
     use_poly_api_key: bool,
+    /// Number of attempts, the first one included, to make at a CurseForge
+    /// API request or file download before giving up.
+    #[clap(long, default_value_t = 5, global = true)]
+    retry_attempts: u32,
+    /// Base delay, in milliseconds, used for the exponential backoff between
+    /// retries.
+    #[clap(long, default_value_t = 500, global = true)]
+    retry_base_delay_ms: u64,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Install a CurseForge modpack zip into a target instance directory
+    Install {
+        /// The zip file to install from
+        #[clap(required = true)]
+        modpack_zip: PathBuf,
+        /// The target directory to install to
+        #[arg(required = true)]
+        target: PathBuf,
+    },
+    /// Repackage an installed instance directory back into a CurseForge modpack zip
+    Export {
+        /// The installed instance directory to export
+        #[clap(required = true)]
+        source: PathBuf,
+        /// Path to write the resulting modpack zip to
+        #[arg(required = true)]
+        output: PathBuf,
+        /// Name to record in the generated manifest.json
+        #[clap(long)]
+        name: String,
+        /// Version to record in the generated manifest.json
+        #[clap(long)]
+        version: String,
+        /// Author to record in the generated manifest.json
+        #[clap(long)]
+        author: String,
+    },
 }
 
 fn setup_logging(log_level: log::LevelFilter) -> crate::error::Result<()> {
@@ -101,9 +137,31 @@ async fn main() -> crate::error::Result<()> {
 
     setup_logging(args.log_level)?;
 
-    let mut modpack = CurseModpack::load(&args.modpack_zip)?;
-    info!("Loaded modpack: {}", modpack);
-    modpack.install_to(&args.target).await?;
+    match args.command {
+        Command::Install {
+            modpack_zip,
+            target,
+        } => {
+            let mut modpack = CurseModpack::load(&modpack_zip)?;
+            info!("Loaded modpack: {}", modpack);
+            modpack
+                .install_to(
+                    &target,
+                    args.retry_attempts,
+                    Duration::from_millis(args.retry_base_delay_ms),
+                )
+                .await?;
+        }
+        Command::Export {
+            source,
+            output,
+            name,
+            version,
+            author,
+        } => {
+            CurseModpack::export_to(&source, &output, &name, &version, &author).await?;
+        }
+    }
 
     info!("Done!");
 
@@ -1,4 +1,11 @@
-use std::path::{Component, Path};
+use std::{
+    future::Future,
+    path::{Component, Path},
+    time::Duration,
+};
+
+use log::warn;
+use rand::Rng;
 
 /// Function taken from the [zip](https://docs.rs/zip/0.6.3/src/zip/read.rs.html#896-911) crate source.
 /// This function is used to sanitize the file name of a zip entry.F
@@ -18,3 +25,84 @@ pub fn sanitize_zip_filename(filename: &str) -> Option<&Path> {
     }
     Some(path)
 }
+
+/// Upper bound on the computed backoff delay, regardless of `base_delay` and
+/// attempt count. Without this, a generous `--retry-attempts` paired with the
+/// default base delay makes later waits balloon to hours or years, which
+/// looks indistinguishable from the process hanging.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Computes the exponential part of the backoff delay for `attempt`
+/// (0-indexed), capped at [`MAX_RETRY_DELAY`]. Split out from
+/// `retry_with_backoff` so the doubling and overflow-capping math can be
+/// unit-tested without driving the whole retry loop.
+fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    base_delay
+        .checked_mul(2u32.saturating_pow(attempt))
+        .unwrap_or(MAX_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Calls `op` up to `attempts` times, sleeping `base_delay * 2^n` (capped at
+/// [`MAX_RETRY_DELAY`]) plus a small random jitter between failed attempts.
+/// The CurseForge API is known to intermittently error out or return
+/// incomplete data, so callers that talk to it should wrap those calls with
+/// this instead of failing on the first error. Returns the last error if
+/// every attempt fails.
+pub async fn retry_with_backoff<F, Fut, T>(
+    attempts: u32,
+    base_delay: Duration,
+    mut op: F,
+) -> crate::error::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = crate::error::Result<T>>,
+{
+    let mut last_err = None;
+    for attempt in 0..attempts.max(1) {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                warn!("Attempt {}/{} failed: {}", attempt + 1, attempts, e);
+                last_err = Some(e);
+                if attempt + 1 < attempts {
+                    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..250));
+                    tokio::time::sleep(backoff_delay(base_delay, attempt) + jitter).await;
+                }
+            }
+        }
+    }
+    Err(last_err.expect("retry_with_backoff always attempts at least once"))
+}
+
+/// Renders `bytes` as a lowercase hex string, e.g. for comparing a computed
+/// digest against the hex-encoded hash a server returned.
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delay_doubles_each_attempt() {
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff_delay(base, 0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(base, 1), Duration::from_millis(1000));
+        assert_eq!(backoff_delay(base, 2), Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn backoff_delay_caps_at_max() {
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff_delay(base, 19), MAX_RETRY_DELAY);
+        assert_eq!(backoff_delay(base, 30), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn backoff_delay_does_not_overflow_on_huge_attempt_counts() {
+        let base = Duration::from_secs(1);
+        assert_eq!(backoff_delay(base, u32::MAX), MAX_RETRY_DELAY);
+    }
+}